@@ -2,47 +2,114 @@ use core::str;
 use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
 use crossterm::terminal::{disable_raw_mode, Clear, ClearType};
 use crossterm::terminal::{enable_raw_mode, size};
+use crossterm::style::{Attribute, Color, ResetColor, SetAttribute, SetForegroundColor};
 use crossterm::{cursor, execute, queue};
+use ropey::Rope;
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io::{stdout, ErrorKind, Result, Write};
-use std::path::Path;
-use std::time::Duration;
-use std::{cmp, env, fs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use std::{cmp, env, fs, thread};
 
-struct Reader;
+const QUIT_TIMES: u8 = 3;
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+
+enum CEvent {
+    Key(KeyEvent),
+    Resize(u16, u16),
+    Tick,
+}
+
+struct Reader {
+    events: mpsc::Receiver<CEvent>,
+}
 
 impl Reader {
-    fn read_key(&self) -> Result<KeyEvent> {
-        loop {
-            if poll(Duration::from_millis(500))? {
-                if let Event::Key(event) = read()? {
-                    return Ok(event);
+    fn new() -> Self {
+        let (sender, events) = mpsc::channel();
+
+        let input_sender = sender.clone();
+        thread::spawn(move || loop {
+            if matches!(poll(Duration::from_millis(200)), Ok(true)) {
+                let forwarded = match read() {
+                    Ok(Event::Key(event)) => Some(CEvent::Key(event)),
+                    Ok(Event::Resize(cols, rows)) => Some(CEvent::Resize(cols, rows)),
+                    _ => None,
+                };
+                if let Some(event) = forwarded {
+                    if input_sender.send(event).is_err() {
+                        return;
+                    }
                 }
             }
-        }
+        });
+
+        thread::spawn(move || loop {
+            thread::sleep(TICK_INTERVAL);
+            if sender.send(CEvent::Tick).is_err() {
+                return;
+            }
+        });
+
+        Self { events }
+    }
+
+    fn next_event(&self) -> Result<CEvent> {
+        self.events
+            .recv()
+            .map_err(|_| ErrorKind::BrokenPipe.into())
     }
 }
 
 struct Editor {
     reader: Reader,
     output: Output,
+    quit_times: u8,
 }
 
 impl Editor {
     fn new() -> Self {
         Self {
-            reader: Reader,
+            reader: Reader::new(),
             output: Output::new(),
+            quit_times: QUIT_TIMES,
         }
     }
 
-    fn process_keypress(&mut self) -> Result<bool> {
-        match self.reader.read_key()? {
+    fn process_keypress(&mut self, key_event: KeyEvent) -> Result<bool> {
+        match key_event {
             KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::CONTROL,
                 ..
-            } => return Ok(false),
+            } => {
+                if self.output.editor_rows.dirty > 0 && self.quit_times > 0 {
+                    self.quit_times -= 1;
+                    if self.quit_times > 0 {
+                        self.output.set_status_message(&format!(
+                            "File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                            self.quit_times
+                        ));
+                        return Ok(true);
+                    }
+                }
+                return Ok(false);
+            }
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                if self.output.editor_rows.filename.is_some() {
+                    self.output.editor_rows.save()?;
+                    self.output.set_status_message("File saved successfully");
+                } else {
+                    self.output.set_status_message("No file name; nothing saved");
+                }
+            }
             KeyEvent {
                 code:
                     direction @ (KeyCode::Up
@@ -76,14 +143,135 @@ impl Editor {
                     })
                 })
             }
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => self.output.insert_newline(),
+            KeyEvent {
+                code: key @ (KeyCode::Backspace | KeyCode::Delete),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => self.output.delete_char(key),
+            KeyEvent {
+                code: KeyCode::Char(ch),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                ..
+            } => self.output.insert_char(ch),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.find()?,
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.output.undo(),
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.output.redo(),
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.output.show_line_numbers = !self.output.show_line_numbers,
             _ => {}
         }
+        self.quit_times = QUIT_TIMES;
         Ok(true)
     }
 
+    fn find(&mut self) -> Result<()> {
+        let saved_cursor_controller = self.output.cursor_controller;
+        let mut query = String::new();
+        let mut search_index = SearchIndex::new();
+        'search: loop {
+            self.output
+                .set_status_message(&format!("Search: {} (Use ESC/Arrows/Enter)", query));
+            self.output.refresh_screen()?;
+            match self.reader.next_event()? {
+                CEvent::Key(key_event) => match key_event {
+                    KeyEvent {
+                        code: KeyCode::Esc, ..
+                    } => {
+                        self.output.cursor_controller = saved_cursor_controller;
+                        self.output.search_match = None;
+                        break 'search;
+                    }
+                    KeyEvent {
+                        code: KeyCode::Enter,
+                        ..
+                    } => {
+                        self.output.search_match = None;
+                        break 'search;
+                    }
+                    KeyEvent {
+                        code: KeyCode::Backspace | KeyCode::Delete,
+                        ..
+                    } => {
+                        query.pop();
+                        search_index.last_match = None;
+                        self.output.find_callback(&query, &mut search_index);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Up, ..
+                    } => {
+                        search_index.direction = -1;
+                        self.output.find_callback(&query, &mut search_index);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Down,
+                        ..
+                    } => {
+                        search_index.direction = 1;
+                        self.output.find_callback(&query, &mut search_index);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char(ch),
+                        modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                        ..
+                    } => {
+                        query.push(ch);
+                        search_index.last_match = None;
+                        self.output.find_callback(&query, &mut search_index);
+                    }
+                    _ => {}
+                },
+                CEvent::Resize(cols, rows) => self.output.handle_resize(cols, rows),
+                CEvent::Tick => {}
+            }
+        }
+        self.output.set_status_message("");
+        Ok(())
+    }
+
     fn run(&mut self) -> Result<bool> {
         self.output.refresh_screen()?;
-        self.process_keypress()
+        match self.reader.next_event()? {
+            CEvent::Key(key_event) => self.process_keypress(key_event),
+            CEvent::Resize(cols, rows) => {
+                self.output.handle_resize(cols, rows);
+                Ok(true)
+            }
+            CEvent::Tick => Ok(true),
+        }
+    }
+}
+
+struct SearchIndex {
+    last_match: Option<usize>,
+    direction: i8,
+}
+
+impl SearchIndex {
+    fn new() -> Self {
+        Self {
+            last_match: None,
+            direction: 1,
+        }
     }
 }
 
@@ -99,20 +287,77 @@ impl Drop for CleanUp {
 struct Row {
     row_content: Box<str>,
     render: String,
+    highlight: Vec<HlKind>,
 }
 
-impl Row {
-    fn new(row_content: Box<str>, render: String) -> Self {
-        Self {
-            row_content,
-            render,
-        }
-    }
+const TAB_STOP: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HlKind {
+    Normal,
+    Number,
+    String,
+    Comment,
+    Keyword,
+    Match,
 }
 
-const TAB_STOP: usize = 8;
+/// Keyword set, comment token, and feature flags for one language. New
+/// languages are added here rather than by touching the renderer.
+struct SyntaxConfig {
+    file_extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    comment_start: &'static str,
+    highlight_numbers: bool,
+    highlight_strings: bool,
+}
+
+const RUST_SYNTAX: SyntaxConfig = SyntaxConfig {
+    file_extensions: &["rs"],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while",
+    ],
+    comment_start: "//",
+    highlight_numbers: true,
+    highlight_strings: true,
+};
+
+const SYNTAXES: &[&SyntaxConfig] = &[&RUST_SYNTAX];
+
+fn detect_syntax(filename: Option<&Path>) -> Option<&'static SyntaxConfig> {
+    let extension = filename?.extension()?.to_str()?;
+    SYNTAXES
+        .iter()
+        .find(|syntax| syntax.file_extensions.contains(&extension))
+        .copied()
+}
+
+/// A single reversible buffer mutation, as pushed onto `EditorRows::undo_stack`.
+#[derive(Clone, Copy)]
+enum Change {
+    InsertChar { y: usize, x: usize, ch: char },
+    DeleteChar { y: usize, x: usize, ch: char },
+    SplitLine { y: usize, x: usize },
+    JoinLine { y: usize, x: usize },
+}
+
+/// Backed by a rope so edits are O(log n) instead of shifting a `Vec<Row>`.
+/// `render_cache` holds the tab-expanded text and highlight kinds for lines
+/// that have been rendered (drawn or searched) since their last edit, keyed
+/// by line index; an edit only evicts the
+/// entries for the lines it actually touches (or, when the edit shifts line
+/// indices, everything from that point on).
 struct EditorRows {
-    row_contents: Vec<Row>,
+    rope: Rope,
+    filename: Option<PathBuf>,
+    dirty: usize,
+    render_cache: RefCell<HashMap<usize, (String, Vec<HlKind>)>>,
+    undo_stack: Vec<Vec<Change>>,
+    redo_stack: Vec<Vec<Change>>,
+    syntax: Option<&'static SyntaxConfig>,
 }
 
 impl EditorRows {
@@ -121,57 +366,386 @@ impl EditorRows {
 
         match arg.nth(1) {
             None => Self {
-                row_contents: Vec::new(),
+                rope: Rope::new(),
+                filename: None,
+                dirty: 0,
+                render_cache: RefCell::new(HashMap::new()),
+                undo_stack: Vec::new(),
+                redo_stack: Vec::new(),
+                syntax: None,
             },
-            Some(file) => Self::from_file(file.as_ref()),
+            Some(file) => Self::from_file(file.into()),
         }
     }
 
-    fn from_file(file: &Path) -> Self {
-        let file_contents = fs::read_to_string(file).expect("Unable to read file");
+    fn from_file(file: PathBuf) -> Self {
+        let file_contents = fs::read_to_string(&file).expect("Unable to read file");
+        let syntax = detect_syntax(Some(&file));
         Self {
-            row_contents: file_contents
-                .lines()
-                .map(|it| {
-                    let mut row = Row::new(it.into(), String::new());
-                    Self::render_row(&mut row);
-                    row
-                })
-                .collect(),
+            rope: Rope::from_str(&file_contents),
+            filename: Some(file),
+            dirty: 0,
+            render_cache: RefCell::new(HashMap::new()),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            syntax,
         }
     }
 
     fn number_of_rows(&self) -> usize {
-        self.row_contents.len()
+        if self.rope.len_chars() == 0 {
+            return 0;
+        }
+        let lines = self.rope.len_lines();
+        if self.rope.line(lines - 1).len_chars() == 0 {
+            lines - 1
+        } else {
+            lines
+        }
     }
 
-    fn get_render(&self, at: usize) -> &str {
-        &self.row_contents[at].render
+    fn line_content(&self, at: usize) -> String {
+        let mut content = self.rope.line(at).to_string();
+        if content.ends_with('\n') {
+            content.pop();
+            if content.ends_with('\r') {
+                content.pop();
+            }
+        }
+        content
     }
 
-    fn get_editor_row(&self, at: usize) -> &Row {
-        &self.row_contents[at]
+    fn char_idx(&self, cursor_y: usize, cursor_x: usize) -> usize {
+        self.rope.line_to_char(cursor_y) + cursor_x
     }
 
-    fn render_row(row: &mut Row) {
-        let mut index = 0;
-        let capacity = row
-            .row_content
-            .chars()
-            .fold(0, |acc, next| acc + if next == '\t' { TAB_STOP } else { 1 });
-        row.render = String::with_capacity(capacity);
-        row.row_content.chars().for_each(|c| {
-            index += 1;
-            if c == '\t' {
-                row.render.push(' ');
-                while index % TAB_STOP != 0 {
-                    row.render.push(' ');
-                    index += 1
+    fn get_render(&self, at: usize) -> String {
+        self.rendered(at).0
+    }
+
+    fn rendered(&self, at: usize) -> (String, Vec<HlKind>) {
+        if let Some(entry) = self.render_cache.borrow().get(&at) {
+            return entry.clone();
+        }
+        let entry = Self::classify(&self.line_content(at), self.syntax);
+        self.render_cache.borrow_mut().insert(at, entry.clone());
+        entry
+    }
+
+    fn get_editor_row(&self, at: usize) -> Row {
+        let (render, highlight) = self.rendered(at);
+        Row {
+            row_content: self.line_content(at).into(),
+            render,
+            highlight,
+        }
+    }
+
+    fn invalidate_render(&mut self, at: usize) {
+        self.render_cache.borrow_mut().remove(&at);
+    }
+
+    fn invalidate_render_from(&mut self, at: usize) {
+        self.render_cache.borrow_mut().retain(|&line, _| line < at);
+    }
+
+    fn insert_char_raw(&mut self, cursor_y: usize, cursor_x: usize, ch: char) {
+        let idx = self.char_idx(cursor_y, cursor_x);
+        self.rope.insert_char(idx, ch);
+        if ch == '\n' {
+            self.invalidate_render_from(cursor_y);
+        } else {
+            self.invalidate_render(cursor_y);
+        }
+        self.dirty += 1;
+    }
+
+    fn delete_char_raw(&mut self, cursor_y: usize, cursor_x: usize) -> char {
+        let idx = self.char_idx(cursor_y, cursor_x);
+        let ch = self.rope.char(idx);
+        self.rope.remove(idx..idx + 1);
+        if ch == '\n' {
+            self.invalidate_render_from(cursor_y);
+        } else {
+            self.invalidate_render(cursor_y);
+        }
+        self.dirty += 1;
+        ch
+    }
+
+    fn record(&mut self, change: Change) {
+        self.redo_stack.clear();
+        if let Some(group) = self.undo_stack.last_mut() {
+            if Self::coalesces(group.last(), &change) {
+                group.push(change);
+                return;
+            }
+        }
+        self.undo_stack.push(vec![change]);
+    }
+
+    fn coalesces(last: Option<&Change>, change: &Change) -> bool {
+        match (last, change) {
+            (Some(Change::InsertChar { y: gy, x: gx, .. }), Change::InsertChar { y, x, .. }) => {
+                gy == y && *x == gx + 1
+            }
+            (Some(Change::DeleteChar { y: gy, x: gx, .. }), Change::DeleteChar { y, x, .. }) => {
+                gy == y && (x == gx || *x + 1 == *gx)
+            }
+            _ => false,
+        }
+    }
+
+    fn insert_char(&mut self, at: (usize, usize), ch: char) {
+        let (y, x) = at;
+        if y >= self.rope.len_lines() {
+            // Cursor is parked past the last real rope line (the file's last
+            // line has no trailing newline). Materialize that row first so
+            // the char lands on it instead of being appended to the
+            // previous line while the cursor stays on the phantom row.
+            let prev_row = y - 1;
+            let prev_len = self.line_content(prev_row).chars().count();
+            self.insert_char_raw(prev_row, prev_len, '\n');
+            self.record(Change::SplitLine {
+                y: prev_row,
+                x: prev_len,
+            });
+        }
+        self.insert_char_raw(y, x, ch);
+        self.record(Change::InsertChar { y, x, ch });
+    }
+
+    fn delete_char(&mut self, at: (usize, usize)) {
+        let (y, x) = at;
+        if x > 0 {
+            let ch = self.delete_char_raw(y, x - 1);
+            self.record(Change::DeleteChar { y, x: x - 1, ch });
+        } else {
+            let previous_len = self.line_content(y - 1).chars().count();
+            self.delete_char_raw(y - 1, previous_len);
+            self.record(Change::JoinLine {
+                y: y - 1,
+                x: previous_len,
+            });
+        }
+    }
+
+    fn delete_char_forward(&mut self, at: (usize, usize)) {
+        let (y, x) = at;
+        let row_len = self.line_content(y).chars().count();
+        if x < row_len {
+            let ch = self.delete_char_raw(y, x);
+            self.record(Change::DeleteChar { y, x, ch });
+        } else {
+            self.delete_char_raw(y, x);
+            self.record(Change::JoinLine { y, x });
+        }
+    }
+
+    fn insert_newline(&mut self, at: (usize, usize)) {
+        let (y, x) = at;
+        self.insert_char_raw(y, x, '\n');
+        self.record(Change::SplitLine { y, x });
+    }
+
+    fn apply_inverse(&mut self, change: Change) -> (usize, usize) {
+        match change {
+            Change::InsertChar { y, x, .. } => {
+                self.delete_char_raw(y, x);
+                (y, x)
+            }
+            Change::DeleteChar { y, x, ch } => {
+                self.insert_char_raw(y, x, ch);
+                (y, x + 1)
+            }
+            Change::SplitLine { y, x } => {
+                self.delete_char_raw(y, x);
+                (y, x)
+            }
+            Change::JoinLine { y, x } => {
+                self.insert_char_raw(y, x, '\n');
+                (y + 1, 0)
+            }
+        }
+    }
+
+    fn apply(&mut self, change: Change) -> (usize, usize) {
+        match change {
+            Change::InsertChar { y, x, ch } => {
+                self.insert_char_raw(y, x, ch);
+                (y, x + 1)
+            }
+            Change::DeleteChar { y, x, .. } => {
+                self.delete_char_raw(y, x);
+                (y, x)
+            }
+            Change::SplitLine { y, x } => {
+                self.insert_char_raw(y, x, '\n');
+                (y + 1, 0)
+            }
+            Change::JoinLine { y, x } => {
+                self.delete_char_raw(y, x);
+                (y, x)
+            }
+        }
+    }
+
+    fn undo(&mut self) -> Option<(usize, usize)> {
+        let group = self.undo_stack.pop()?;
+        let mut cursor = (0, 0);
+        for change in group.iter().rev() {
+            cursor = self.apply_inverse(*change);
+        }
+        self.redo_stack.push(group);
+        Some(cursor)
+    }
+
+    fn redo(&mut self) -> Option<(usize, usize)> {
+        let group = self.redo_stack.pop()?;
+        let mut cursor = (0, 0);
+        for change in group.iter() {
+            cursor = self.apply(*change);
+        }
+        self.undo_stack.push(group);
+        Some(cursor)
+    }
+
+    fn save(&mut self) -> Result<()> {
+        match &self.filename {
+            None => Ok(()),
+            Some(name) => {
+                fs::write(name, self.rope.to_string())?;
+                self.dirty = 0;
+                Ok(())
+            }
+        }
+    }
+
+    fn push_rendered(
+        render: &mut String,
+        highlight: &mut Vec<HlKind>,
+        column: &mut usize,
+        c: char,
+        kind: HlKind,
+    ) {
+        *column += 1;
+        if c == '\t' {
+            render.push(' ');
+            highlight.push(kind);
+            while !(*column).is_multiple_of(TAB_STOP) {
+                render.push(' ');
+                highlight.push(kind);
+                *column += 1;
+            }
+        } else {
+            render.push(c);
+            highlight.push(kind);
+        }
+    }
+
+    /// Tab-expands `content` into `render` while classifying each rendered
+    /// character into an `HlKind`, so `draw_rows` only has to react to kind
+    /// changes rather than re-lexing. Numbers, string/char literals (with
+    /// open-quote state tracked across the row), comments, and keywords from
+    /// `syntax` are recognised; everything else, or a `None` syntax, is
+    /// `HlKind::Normal`.
+    fn classify(content: &str, syntax: Option<&SyntaxConfig>) -> (String, Vec<HlKind>) {
+        let mut render = String::new();
+        let mut highlight = Vec::new();
+        let mut column = 0;
+
+        let Some(syntax) = syntax else {
+            content
+                .chars()
+                .for_each(|c| Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::Normal));
+            return (render, highlight);
+        };
+
+        let chars: Vec<char> = content.chars().collect();
+        let comment_start: Vec<char> = syntax.comment_start.chars().collect();
+        let mut in_string: Option<char> = None;
+        let mut in_comment = false;
+        // Kilo-style separator tracking: a digit only starts (or continues) a
+        // number run if it follows a separator or another number, so "42"
+        // highlights fully instead of just its leading digit.
+        let mut prev_sep = true;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+
+            if in_comment {
+                Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::Comment);
+                i += 1;
+                continue;
+            }
+
+            if in_string.is_none()
+                && !comment_start.is_empty()
+                && chars[i..].starts_with(comment_start.as_slice())
+            {
+                in_comment = true;
+                continue;
+            }
+
+            if syntax.highlight_strings {
+                if let Some(quote) = in_string {
+                    Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::String);
+                    if c == quote && chars[i.saturating_sub(1)] != '\\' {
+                        in_string = None;
+                    }
+                    i += 1;
+                    prev_sep = true;
+                    continue;
+                } else if c == '"' || c == '\'' {
+                    in_string = Some(c);
+                    Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::String);
+                    i += 1;
+                    prev_sep = false;
+                    continue;
                 }
-            } else {
-                row.render.push(c);
             }
-        });
+
+            let prev_was_number = highlight.last() == Some(&HlKind::Number);
+            if syntax.highlight_numbers
+                && ((c.is_ascii_digit() && (prev_sep || prev_was_number))
+                    || (c == '.' && prev_was_number))
+            {
+                Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::Number);
+                i += 1;
+                prev_sep = false;
+                continue;
+            }
+
+            if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let kind = if syntax.keywords.contains(&word.as_str()) {
+                    HlKind::Keyword
+                } else {
+                    HlKind::Normal
+                };
+                word.chars().for_each(|wc| {
+                    Self::push_rendered(&mut render, &mut highlight, &mut column, wc, kind)
+                });
+                prev_sep = false;
+                continue;
+            }
+
+            Self::push_rendered(&mut render, &mut highlight, &mut column, c, HlKind::Normal);
+            prev_sep = Self::is_separator(c);
+            i += 1;
+        }
+
+        (render, highlight)
+    }
+
+    /// Mirrors kilo's `is_separator`: whitespace and common punctuation reset
+    /// a number run so e.g. `foo123` doesn't highlight the digits.
+    fn is_separator(c: char) -> bool {
+        c.is_whitespace() || c == '\0' || ",.()+-/*=~%<>[];".contains(c)
     }
 }
 
@@ -214,13 +788,48 @@ impl Write for EditorContents {
     }
 }
 
+struct StatusMessage {
+    message: Option<String>,
+    set_time: Option<Instant>,
+}
+
+impl StatusMessage {
+    fn new(initial_message: String) -> Self {
+        Self {
+            message: Some(initial_message),
+            set_time: Some(Instant::now()),
+        }
+    }
+
+    fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+        self.set_time = Some(Instant::now());
+    }
+
+    fn message(&mut self) -> Option<&String> {
+        self.set_time.and_then(|time| {
+            if time.elapsed() > Duration::from_secs(5) {
+                self.message = None;
+                self.set_time = None;
+                None
+            } else {
+                self.message.as_ref()
+            }
+        })
+    }
+}
+
 struct Output {
     win_size: (usize, usize),
     editor_contents: EditorContents,
     cursor_controller: CursorController,
     editor_rows: EditorRows,
+    status_message: StatusMessage,
+    search_match: Option<(usize, usize, usize)>,
+    show_line_numbers: bool,
 }
 
+#[derive(Clone, Copy)]
 struct CursorController {
     cursor_x: usize,
     cursor_y: usize,
@@ -237,7 +846,7 @@ impl CursorController {
             cursor_x: 0,
             cursor_y: 0,
             screen_columns: win_size.0,
-            screen_rows: win_size.1,
+            screen_rows: win_size.1.saturating_sub(2),
             row_offset: 0,
             column_offset: 0,
             render_x: 0,
@@ -298,7 +907,7 @@ impl CursorController {
     fn scroll(&mut self, editor_rows: &EditorRows) {
         self.render_x = 0;
         if self.cursor_y < editor_rows.number_of_rows() {
-            self.render_x = self.get_render_x(editor_rows.get_editor_row(self.cursor_y))
+            self.render_x = self.get_render_x(&editor_rows.get_editor_row(self.cursor_y))
         }
 
         self.row_offset = cmp::min(self.row_offset, self.cursor_y);
@@ -323,6 +932,20 @@ impl CursorController {
                 }
             })
     }
+
+    fn render_x_to_cursor_x(row: &Row, render_x: usize) -> usize {
+        let mut current_render_x = 0;
+        for (cursor_x, c) in row.row_content.chars().enumerate() {
+            if c == '\t' {
+                current_render_x += (TAB_STOP - 1) - (current_render_x % TAB_STOP);
+            }
+            current_render_x += 1;
+            if current_render_x > render_x {
+                return cursor_x;
+            }
+        }
+        row.row_content.len()
+    }
 }
 
 impl Output {
@@ -334,6 +957,67 @@ impl Output {
             editor_contents: EditorContents::new(),
             cursor_controller: CursorController::new(win_size),
             editor_rows: EditorRows::new(),
+            status_message: StatusMessage::new(
+                "HELP: Ctrl-S = Save | Ctrl-Q = Quit | Ctrl-F = Find".into(),
+            ),
+            search_match: None,
+            show_line_numbers: true,
+        }
+    }
+
+    fn gutter_width(&self) -> usize {
+        if !self.show_line_numbers {
+            return 0;
+        }
+        let digits = self.editor_rows.number_of_rows().max(1).ilog10() as usize + 1;
+        digits + 1
+    }
+
+    fn set_status_message(&mut self, message: &str) {
+        self.status_message.set_message(message.into());
+    }
+
+    fn handle_resize(&mut self, cols: u16, rows: u16) {
+        self.win_size = (cols as usize, rows as usize);
+        self.cursor_controller.screen_columns = self.win_size.0;
+        self.cursor_controller.screen_rows = self.win_size.1.saturating_sub(2);
+        self.cursor_controller.row_offset = cmp::min(
+            self.cursor_controller.row_offset,
+            self.cursor_controller.cursor_y,
+        );
+        self.cursor_controller.column_offset = cmp::min(
+            self.cursor_controller.column_offset,
+            self.cursor_controller.cursor_x,
+        );
+    }
+
+    fn find_callback(&mut self, query: &str, search_index: &mut SearchIndex) {
+        if query.is_empty() {
+            return;
+        }
+        let num_of_rows = self.editor_rows.number_of_rows();
+        let mut current: isize = match search_index.last_match {
+            Some(last) => last as isize,
+            None => -1,
+        };
+        for _ in 0..num_of_rows {
+            current += search_index.direction as isize;
+            if current == -1 {
+                current = num_of_rows as isize - 1;
+            } else if current == num_of_rows as isize {
+                current = 0;
+            }
+            let current = current as usize;
+            let row = self.editor_rows.get_editor_row(current);
+            if let Some(render_x) = row.render.find(query) {
+                search_index.last_match = Some(current);
+                self.cursor_controller.cursor_y = current;
+                self.cursor_controller.cursor_x =
+                    CursorController::render_x_to_cursor_x(&row, render_x);
+                self.cursor_controller.row_offset = self.editor_rows.number_of_rows();
+                self.search_match = Some((current, render_x, query.len()));
+                break;
+            }
         }
     }
 
@@ -346,7 +1030,10 @@ impl Output {
         self.cursor_controller.scroll(&self.editor_rows);
         queue!(self.editor_contents, cursor::Hide, cursor::MoveTo(0, 0))?;
         self.draw_rows();
-        let cursor_x = self.cursor_controller.render_x - self.cursor_controller.column_offset;
+        self.draw_status_bar();
+        self.draw_message_bar();
+        let cursor_x = self.cursor_controller.render_x - self.cursor_controller.column_offset
+            + self.gutter_width();
         let cursor_y = self.cursor_controller.cursor_y - self.cursor_controller.row_offset;
         queue!(
             self.editor_contents,
@@ -357,47 +1044,208 @@ impl Output {
     }
 
     fn draw_rows(&mut self) {
-        let screen_rows = self.win_size.1;
-        let screen_columns = self.win_size.0;
+        let screen_rows = self.win_size.1.saturating_sub(2);
+        let gutter_width = self.gutter_width();
+        let text_columns = self.win_size.0.saturating_sub(gutter_width);
         for i in 0..screen_rows {
             let file_row = i + self.cursor_controller.row_offset;
             if file_row >= self.editor_rows.number_of_rows() {
+                if gutter_width > 0 {
+                    self.editor_contents.push('~');
+                    (1..gutter_width).for_each(|_| self.editor_contents.push(' '));
+                }
                 if self.editor_rows.number_of_rows() == 0 && i == screen_rows / 3 {
                     let mut welcome = format!("Pound Editor --- Version {}", "3.0.0");
-                    if welcome.len() > screen_columns {
-                        welcome.truncate(screen_columns);
+                    if welcome.len() > text_columns {
+                        welcome.truncate(text_columns);
                     }
-                    let mut padding = (screen_columns - welcome.len()) / 2;
-                    if padding != 0 {
+                    let mut padding = (text_columns - welcome.len()) / 2;
+                    if gutter_width == 0 && padding != 0 {
                         self.editor_contents.push('~');
                         padding -= 1;
                     }
                     (0..padding).for_each(|_| self.editor_contents.push(' '));
                     self.editor_contents.push_str(&welcome);
-                } else {
+                } else if gutter_width == 0 {
                     self.editor_contents.push('~');
                 }
             } else {
-                let row = self.editor_rows.get_render(file_row);
+                if gutter_width > 0 {
+                    let number = format!("{:>width$} ", file_row + 1, width = gutter_width - 1);
+                    queue!(self.editor_contents, SetForegroundColor(Color::DarkGrey)).unwrap();
+                    self.editor_contents.push_str(&number);
+                    queue!(self.editor_contents, ResetColor).unwrap();
+                }
+                let row = self.editor_rows.get_editor_row(file_row);
                 let column_offset = self.cursor_controller.column_offset;
 
-                let len: usize = cmp::min(row.len().saturating_sub(column_offset), screen_columns);
+                let len: usize =
+                    cmp::min(row.render.len().saturating_sub(column_offset), text_columns);
                 let start: usize = if len == 0 { 0 } else { column_offset };
-                self.editor_contents
-                    .push_str(&self.editor_rows.get_render(file_row)[start..start + len]);
+                let match_range = self
+                    .search_match
+                    .filter(|(y, ..)| *y == file_row)
+                    .map(|(_, match_start, match_len)| match_start..match_start + match_len);
+
+                let mut current: Option<HlKind> = None;
+                for (offset, c) in row.render[start..start + len].chars().enumerate() {
+                    let column = start + offset;
+                    let kind = match &match_range {
+                        Some(range) if range.contains(&column) => HlKind::Match,
+                        _ => row.highlight[column],
+                    };
+                    if current != Some(kind) {
+                        match Self::color_for(kind) {
+                            Some(color) => {
+                                queue!(self.editor_contents, SetForegroundColor(color)).unwrap()
+                            }
+                            None => queue!(self.editor_contents, ResetColor).unwrap(),
+                        }
+                        current = Some(kind);
+                    }
+                    self.editor_contents.push(c);
+                }
+                if current.is_some() {
+                    queue!(self.editor_contents, ResetColor).unwrap();
+                }
             }
 
             queue!(self.editor_contents, Clear(ClearType::UntilNewLine)).unwrap();
-            if i < screen_rows - 1 {
-                self.editor_contents.push_str("\r\n");
+            self.editor_contents.push_str("\r\n");
+        }
+    }
+
+    fn color_for(kind: HlKind) -> Option<Color> {
+        match kind {
+            HlKind::Normal => None,
+            HlKind::Number => Some(Color::Magenta),
+            HlKind::String => Some(Color::Green),
+            HlKind::Comment => Some(Color::DarkGrey),
+            HlKind::Keyword => Some(Color::Yellow),
+            HlKind::Match => Some(Color::Blue),
+        }
+    }
+
+    fn draw_status_bar(&mut self) {
+        queue!(self.editor_contents, SetAttribute(Attribute::Reverse)).unwrap();
+        let info = format!(
+            "{} -- {} lines {}",
+            self.editor_rows
+                .filename
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .and_then(|name| name.to_str())
+                .unwrap_or("[No Name]"),
+            self.editor_rows.number_of_rows(),
+            if self.editor_rows.dirty > 0 {
+                "(modified)"
+            } else {
+                ""
+            }
+        );
+        let info_len = cmp::min(info.len(), self.win_size.0);
+        let line_info = format!(
+            "{}/{}",
+            self.cursor_controller.cursor_y + 1,
+            self.editor_rows.number_of_rows()
+        );
+        self.editor_contents.push_str(&info[..info_len]);
+        for i in info_len..self.win_size.0 {
+            if self.win_size.0 - i == line_info.len() {
+                self.editor_contents.push_str(&line_info);
+                break;
+            } else {
+                self.editor_contents.push(' ');
             }
         }
+        queue!(self.editor_contents, SetAttribute(Attribute::Reset)).unwrap();
+        self.editor_contents.push_str("\r\n");
+    }
+
+    fn draw_message_bar(&mut self) {
+        queue!(self.editor_contents, Clear(ClearType::UntilNewLine)).unwrap();
+        if let Some(message) = self.status_message.message() {
+            self.editor_contents
+                .push_str(&message[..cmp::min(self.win_size.0, message.len())]);
+        }
     }
 
     fn move_cursor(&mut self, direction: KeyCode) {
         self.cursor_controller
             .move_cursor(direction, &self.editor_rows);
     }
+
+    fn insert_char(&mut self, ch: char) {
+        self.editor_rows.insert_char(
+            (
+                self.cursor_controller.cursor_y,
+                self.cursor_controller.cursor_x,
+            ),
+            ch,
+        );
+        self.cursor_controller.cursor_x += 1;
+    }
+
+    fn insert_newline(&mut self) {
+        self.editor_rows.insert_newline((
+            self.cursor_controller.cursor_y,
+            self.cursor_controller.cursor_x,
+        ));
+        self.cursor_controller.cursor_y += 1;
+        self.cursor_controller.cursor_x = 0;
+    }
+
+    fn delete_char(&mut self, key: KeyCode) {
+        if self.cursor_controller.cursor_y == self.editor_rows.number_of_rows() {
+            return;
+        }
+        let cursor_y = self.cursor_controller.cursor_y;
+        let cursor_x = self.cursor_controller.cursor_x;
+        match key {
+            KeyCode::Backspace => {
+                if cursor_x == 0 && cursor_y == 0 {
+                    return;
+                }
+                if cursor_x == 0 {
+                    let previous_len = self.editor_rows.get_editor_row(cursor_y - 1).row_content.len();
+                    self.editor_rows.delete_char((cursor_y, cursor_x));
+                    self.cursor_controller.cursor_y -= 1;
+                    self.cursor_controller.cursor_x = previous_len;
+                } else {
+                    self.editor_rows.delete_char((cursor_y, cursor_x));
+                    self.cursor_controller.cursor_x -= 1;
+                }
+            }
+            KeyCode::Delete => {
+                let row_len = self.editor_rows.get_editor_row(cursor_y).row_content.len();
+                if cursor_x == row_len && cursor_y == self.editor_rows.number_of_rows() - 1 {
+                    return;
+                }
+                self.editor_rows.delete_char_forward((cursor_y, cursor_x));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn undo(&mut self) {
+        match self.editor_rows.undo() {
+            Some((y, x)) => {
+                self.cursor_controller.cursor_y = y;
+                self.cursor_controller.cursor_x = x;
+            }
+            None => self.set_status_message("Nothing to undo"),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.editor_rows.redo() {
+            Some((y, x)) => {
+                self.cursor_controller.cursor_y = y;
+                self.cursor_controller.cursor_x = x;
+            }
+            None => self.set_status_message("Nothing to redo"),
+        }
+    }
 }
 
 fn main() -> Result<()> {